@@ -1,9 +1,13 @@
 //! Command line arguments.
-use std::path::PathBuf;
+use std::{fs, path::PathBuf};
 
 use clap::Parser;
+use directories::ProjectDirs;
+use image::Rgba;
 use num::rational::Ratio;
 
+use crate::process::{Format, EGA_PALETTE};
+
 /**
  * Command line arguments.
  */
@@ -11,7 +15,7 @@ use num::rational::Ratio;
 #[command(author, version)]
 #[command(about = "border-producing gizmo", long_about = None)]
 pub struct Args {
-    /// Image file paths.
+    /// Image file paths. Pass `-` to read a single image from stdin.
     #[clap(required = true)]
     pub files: Vec<PathBuf>,
     #[clap(short, long, default_value_t = 400)]
@@ -23,4 +27,329 @@ pub struct Args {
     /// Force orientation (this only applies when the ratio is forced: ratio-matching will always match the orientation).
     #[clap(short = 'o', long)]
     pub force_orientation: bool,
+    /// Border color, as a hex code (`#1e1e1e`), an `rgba(r, g, b, a)` expression, or a named color (e.g. `white`).
+    #[clap(short = 'c', long, default_value = "white", value_parser = parse_color)]
+    pub color: Rgba<u8>,
+    /// Output image format (png, jpeg, or webp); inferred from the file extension if omitted.
+    #[clap(short = 'f', long, value_parser = parse_format)]
+    pub format: Option<Format>,
+    /// Output encoder quality, from 1 (worst) to 100 (best). Only applies to JPEG output.
+    #[clap(short = 'q', long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub quality: u8,
+    /// Reduce the image to a fixed color palette before bordering: either the built-in
+    /// `ega` 16-color set, or a comma-separated list of hex colors (e.g. `"#000,#fff"`).
+    #[clap(long, value_parser = parse_palette)]
+    pub palette: Option<Vec<Rgba<u8>>>,
+    /// Apply Floyd-Steinberg dithering when reducing to a fixed palette. Only applies when `--palette` is set.
+    #[clap(long, requires = "palette")]
+    pub dither: bool,
+    /// Resize images to fit within a `WxH` box (preserving aspect ratio), then border onto a
+    /// canvas of exactly that size, so a batch of differently-sized images all share one output size.
+    #[clap(long, value_parser = parse_fit)]
+    pub fit: Option<(u32, u32)>,
+    /// Encode the bordered image to stdout instead of saving it to a `_bordered` sibling file.
+    #[clap(long)]
+    pub stdout: bool,
+    /// Candidate aspect ratios to snap to (e.g. `"16:9,4:3,1:1"`), overriding both the
+    /// built-in common-ratios list and any ratios from a config file.
+    #[clap(long, value_parser = parse_ratios)]
+    pub ratios: Option<Vec<Ratio<u32>>>,
+}
+
+impl Args {
+    /// Resolve the candidate aspect ratios to snap to, in priority order:
+    /// `--ratios`, then the user's config file, then none (so callers fall back to the
+    /// built-in common-ratios list).
+    ///
+    /// # Returns
+    /// The resolved ratio list, or none if neither source provided one.
+    pub fn ratios(&self) -> Option<Vec<Ratio<u32>>> {
+        self.ratios.clone().or_else(config_ratios)
+    }
+}
+
+/// Locate and parse the user's aspect-ratio config file, if one exists.
+///
+/// The file is searched for at `<config dir>/bpg/ratios.conf`, where `<config dir>` is the
+/// platform's standard user config directory (e.g. `~/.config` on Linux). Each non-empty,
+/// non-comment line holds one `W:H` ratio; lines starting with `#` are comments, and lines
+/// that fail to parse are silently skipped.
+///
+/// # Returns
+/// The parsed ratio list, or none if no config file was found.
+fn config_ratios() -> Option<Vec<Ratio<u32>>> {
+    let path = ProjectDirs::from("", "", "bpg")?.config_dir().join("ratios.conf");
+    let contents = fs::read_to_string(path).ok()?;
+    Some(parse_ratios_config(&contents))
+}
+
+/// Parse the contents of a ratio config file into a ratio list.
+///
+/// Each non-empty, non-comment line holds one `W:H` ratio; lines starting with `#` are
+/// comments, and lines that fail to parse are silently skipped.
+fn parse_ratios_config(contents: &str) -> Vec<Ratio<u32>> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| parse_ratio(line).ok())
+        .collect()
+}
+
+/// Parse a single `W:H` ratio.
+fn parse_ratio(raw: &str) -> Result<Ratio<u32>, String> {
+    let (numer, denom) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("expected \"W:H\", found \"{raw}\""))?;
+    let numer = numer
+        .parse::<u32>()
+        .map_err(|_| format!("invalid numerator: \"{numer}\""))?;
+    let denom = denom
+        .parse::<u32>()
+        .map_err(|_| format!("invalid denominator: \"{denom}\""))?;
+    if numer == 0 || denom == 0 {
+        return Err(format!("ratio must not have a zero numerator or denominator: \"{raw}\""));
+    }
+    Ok(Ratio::new(numer, denom))
+}
+
+/// Parse a comma-separated list of `W:H` ratios.
+///
+/// # Args
+/// * `raw` - The raw ratios argument, e.g. `"16:9,4:3,1:1"`.
+///
+/// # Returns
+/// The parsed ratio list, or an error message describing why parsing failed.
+fn parse_ratios(raw: &str) -> Result<Vec<Ratio<u32>>, String> {
+    raw.split(',').map(|entry| parse_ratio(entry.trim())).collect()
+}
+
+/// Parse a color argument into an `Rgba<u8>`.
+///
+/// Accepts three forms:
+/// * Hex codes: `#rgb`, `#rrggbb`, or `#rrggbbaa`.
+/// * `rgba(r, g, b, a)` expressions, with `a` in `0..=255`.
+/// * A small set of named colors (`white`, `black`, `transparent`, ...).
+///
+/// # Args
+/// * `raw` - The raw color argument.
+///
+/// # Returns
+/// The parsed color, or an error message describing why parsing failed.
+fn parse_color(raw: &str) -> Result<Rgba<u8>, String> {
+    let trimmed = raw.trim();
+    if let Some(hex) = trimmed.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    if let Some(inner) = trimmed
+        .strip_prefix("rgba(")
+        .and_then(|s| s.strip_suffix(')'))
+    {
+        return parse_rgba_fn(inner);
+    }
+    parse_named(trimmed)
+}
+
+/// Parse a `#rgb`, `#rrggbb`, or `#rrggbbaa` hex code (without the leading `#`).
+fn parse_hex(hex: &str) -> Result<Rgba<u8>, String> {
+    let expand = |c: char| u8::from_str_radix(&format!("{c}{c}"), 16);
+    let channel = |s: &str| u8::from_str_radix(s, 16);
+    match hex.len() {
+        3 => {
+            let mut chars = hex.chars();
+            let r = expand(chars.next().unwrap()).map_err(|e| e.to_string())?;
+            let g = expand(chars.next().unwrap()).map_err(|e| e.to_string())?;
+            let b = expand(chars.next().unwrap()).map_err(|e| e.to_string())?;
+            Ok(Rgba([r, g, b, 255]))
+        }
+        6 => {
+            let r = channel(&hex[0..2]).map_err(|e| e.to_string())?;
+            let g = channel(&hex[2..4]).map_err(|e| e.to_string())?;
+            let b = channel(&hex[4..6]).map_err(|e| e.to_string())?;
+            Ok(Rgba([r, g, b, 255]))
+        }
+        8 => {
+            let r = channel(&hex[0..2]).map_err(|e| e.to_string())?;
+            let g = channel(&hex[2..4]).map_err(|e| e.to_string())?;
+            let b = channel(&hex[4..6]).map_err(|e| e.to_string())?;
+            let a = channel(&hex[6..8]).map_err(|e| e.to_string())?;
+            Ok(Rgba([r, g, b, a]))
+        }
+        _ => Err(format!("invalid hex color: \"#{hex}\"")),
+    }
+}
+
+/// Parse the inner `r, g, b, a` of an `rgba(...)` expression.
+fn parse_rgba_fn(inner: &str) -> Result<Rgba<u8>, String> {
+    let channels: Vec<&str> = inner.split(',').map(str::trim).collect();
+    if channels.len() != 4 {
+        return Err(format!(
+            "expected 4 comma-separated channels in \"rgba({inner})\", found {}",
+            channels.len()
+        ));
+    }
+    let mut parsed = [0u8; 4];
+    for (i, channel) in channels.iter().enumerate() {
+        parsed[i] = channel
+            .parse::<u8>()
+            .map_err(|_| format!("invalid channel value: \"{channel}\""))?;
+    }
+    Ok(Rgba(parsed))
+}
+
+/// Parse a small set of named colors.
+fn parse_named(name: &str) -> Result<Rgba<u8>, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "white" => Ok(Rgba([255, 255, 255, 255])),
+        "black" => Ok(Rgba([0, 0, 0, 255])),
+        "red" => Ok(Rgba([255, 0, 0, 255])),
+        "green" => Ok(Rgba([0, 128, 0, 255])),
+        "blue" => Ok(Rgba([0, 0, 255, 255])),
+        "gray" | "grey" => Ok(Rgba([128, 128, 128, 255])),
+        "transparent" => Ok(Rgba([0, 0, 0, 0])),
+        _ => Err(format!("unrecognized color name: \"{name}\"")),
+    }
+}
+
+/// Parse an output format argument.
+///
+/// # Args
+/// * `raw` - The raw format argument (`png`, `jpeg`/`jpg`, or `webp`).
+///
+/// # Returns
+/// The parsed format, or an error message describing why parsing failed.
+fn parse_format(raw: &str) -> Result<Format, String> {
+    match raw.to_ascii_lowercase().as_str() {
+        "png" => Ok(Format::Png),
+        "jpeg" | "jpg" => Ok(Format::Jpeg),
+        "webp" => Ok(Format::Webp),
+        _ => Err(format!("unrecognized output format: \"{raw}\"")),
+    }
+}
+
+/// Parse a `WxH` fit-box argument.
+///
+/// # Args
+/// * `raw` - The raw fit argument, e.g. `"1920x1080"`.
+///
+/// # Returns
+/// The parsed `(width, height)` box, or an error message describing why parsing failed.
+fn parse_fit(raw: &str) -> Result<(u32, u32), String> {
+    let (width, height) = raw
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected \"WxH\", found \"{raw}\""))?;
+    let width = width
+        .parse::<u32>()
+        .map_err(|_| format!("invalid width: \"{width}\""))?;
+    let height = height
+        .parse::<u32>()
+        .map_err(|_| format!("invalid height: \"{height}\""))?;
+    Ok((width, height))
+}
+
+/// Parse a palette argument into a list of colors.
+///
+/// # Args
+/// * `raw` - Either `ega` for the built-in 16-color palette, or a comma-separated list of hex colors.
+///
+/// # Returns
+/// The parsed palette, or an error message describing why parsing failed.
+fn parse_palette(raw: &str) -> Result<Vec<Rgba<u8>>, String> {
+    if raw.eq_ignore_ascii_case("ega") {
+        return Ok(EGA_PALETTE.to_vec());
+    }
+    raw.split(',')
+        .map(|entry| {
+            let trimmed = entry.trim();
+            let hex = trimmed
+                .strip_prefix('#')
+                .ok_or_else(|| format!("expected a hex color in the palette list, found \"{trimmed}\""))?;
+            parse_hex(hex)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("#fff", Ok(Rgba([255, 255, 255, 255])))]
+    #[case("#000", Ok(Rgba([0, 0, 0, 255])))]
+    #[case("#1e1e1e", Ok(Rgba([30, 30, 30, 255])))]
+    #[case("#1e1e1e80", Ok(Rgba([30, 30, 30, 128])))]
+    #[case("rgba(0, 0, 0, 128)", Ok(Rgba([0, 0, 0, 128])))]
+    #[case("rgba(255,255,255,255)", Ok(Rgba([255, 255, 255, 255])))]
+    #[case("white", Ok(Rgba([255, 255, 255, 255])))]
+    #[case("BLACK", Ok(Rgba([0, 0, 0, 255])))]
+    #[case("transparent", Ok(Rgba([0, 0, 0, 0])))]
+    #[case("#12", Err("invalid hex color: \"#12\"".to_string()))]
+    #[case("rgba(0, 0, 0)", Err("expected 4 comma-separated channels in \"rgba(0, 0, 0)\", found 3".to_string()))]
+    #[case("not-a-color", Err("unrecognized color name: \"not-a-color\"".to_string()))]
+    fn test_parse_color(#[case] raw: &str, #[case] expected: Result<Rgba<u8>, String>) {
+        assert_eq!(parse_color(raw), expected);
+    }
+
+    #[rstest]
+    #[case("png", Ok(Format::Png))]
+    #[case("PNG", Ok(Format::Png))]
+    #[case("jpeg", Ok(Format::Jpeg))]
+    #[case("jpg", Ok(Format::Jpeg))]
+    #[case("webp", Ok(Format::Webp))]
+    #[case("gif", Err("unrecognized output format: \"gif\"".to_string()))]
+    fn test_parse_format(#[case] raw: &str, #[case] expected: Result<Format, String>) {
+        assert_eq!(parse_format(raw), expected);
+    }
+
+    #[rstest]
+    #[case("ega", Ok(EGA_PALETTE.to_vec()))]
+    #[case("EGA", Ok(EGA_PALETTE.to_vec()))]
+    #[case("#000000,#ffffff", Ok(vec![Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])]))]
+    #[case("#f00", Ok(vec![Rgba([255, 0, 0, 255])]))]
+    #[case("000000", Err("expected a hex color in the palette list, found \"000000\"".to_string()))]
+    fn test_parse_palette(#[case] raw: &str, #[case] expected: Result<Vec<Rgba<u8>>, String>) {
+        assert_eq!(parse_palette(raw), expected);
+    }
+
+    #[rstest]
+    #[case("1920x1080", Ok((1920, 1080)))]
+    #[case("800X600", Ok((800, 600)))]
+    #[case("1080", Err("expected \"WxH\", found \"1080\"".to_string()))]
+    #[case("ax1080", Err("invalid width: \"a\"".to_string()))]
+    #[case("1920xb", Err("invalid height: \"b\"".to_string()))]
+    fn test_parse_fit(#[case] raw: &str, #[case] expected: Result<(u32, u32), String>) {
+        assert_eq!(parse_fit(raw), expected);
+    }
+
+    #[rstest]
+    #[case("16:9", Ok(Ratio::new(16, 9)))]
+    #[case("1:1", Ok(Ratio::new(1, 1)))]
+    #[case("16-9", Err("expected \"W:H\", found \"16-9\"".to_string()))]
+    #[case("a:9", Err("invalid numerator: \"a\"".to_string()))]
+    #[case("16:b", Err("invalid denominator: \"b\"".to_string()))]
+    #[case("0:9", Err("ratio must not have a zero numerator or denominator: \"0:9\"".to_string()))]
+    #[case("9:0", Err("ratio must not have a zero numerator or denominator: \"9:0\"".to_string()))]
+    fn test_parse_ratio(#[case] raw: &str, #[case] expected: Result<Ratio<u32>, String>) {
+        assert_eq!(parse_ratio(raw), expected);
+    }
+
+    #[rstest]
+    #[case("16:9,4:3,1:1", Ok(vec![Ratio::new(16, 9), Ratio::new(4, 3), Ratio::new(1, 1)]))]
+    #[case("16:9", Ok(vec![Ratio::new(16, 9)]))]
+    #[case("16:9, 4:3", Ok(vec![Ratio::new(16, 9), Ratio::new(4, 3)]))]
+    #[case("16:9,bad", Err("expected \"W:H\", found \"bad\"".to_string()))]
+    fn test_parse_ratios(#[case] raw: &str, #[case] expected: Result<Vec<Ratio<u32>>, String>) {
+        assert_eq!(parse_ratios(raw), expected);
+    }
+
+    #[rstest]
+    #[case("16:9\n4:3\n1:1", vec![Ratio::new(16, 9), Ratio::new(4, 3), Ratio::new(1, 1)])]
+    #[case("16:9\n# a comment\n\n4:3", vec![Ratio::new(16, 9), Ratio::new(4, 3)])]
+    #[case("  16:9  \nnot-a-ratio\n4:3", vec![Ratio::new(16, 9), Ratio::new(4, 3)])]
+    #[case("", vec![])]
+    fn test_parse_ratios_config(#[case] contents: &str, #[case] expected: Vec<Ratio<u32>>) {
+        assert_eq!(parse_ratios_config(contents), expected);
+    }
 }
@@ -2,44 +2,81 @@ use std::{collections::HashSet, path::Path};
 
 use clap::Parser;
 use image::ImageResult;
+use indicatif::{ProgressBar, ProgressStyle};
 use rayon::iter::{
     IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator,
 };
 
-use bpg::{cli::Args, process::process_and_save_local};
+use bpg::{
+    cli::Args,
+    process::{process_and_save_local, ProcessOptions},
+};
 
 fn main() {
     let args = Args::parse();
 
+    if args.stdout && args.files.len() > 1 {
+        eprintln!("❌ --stdout only supports a single input file, got {}", args.files.len());
+        std::process::exit(1);
+    }
+
     let mut files = HashSet::new();
 
     if !args.files.is_empty() {
+        let ratios = args.ratios();
         files.par_extend(args.files);
 
+        let progress = ProgressBar::new(files.len() as u64);
+        progress.set_style(
+            ProgressStyle::with_template("{bar:40.cyan/blue} {pos}/{len} {elapsed_precise}")
+                .expect("progress bar template is valid"),
+        );
+
         let results: Vec<(&Path, ImageResult<()>)> = files
             .par_iter()
             .map(|file| {
-                (
+                let result = (
                     file.as_path(),
                     process_and_save_local(
                         file.as_path(),
-                        args.border,
-                        args.force_ratio,
-                        args.force_orientation,
+                        ProcessOptions {
+                            border: args.border,
+                            force_ratio: args.force_ratio,
+                            force_orientation: args.force_orientation,
+                            color: args.color,
+                            format: args.format,
+                            quality: args.quality,
+                            palette: args.palette.as_deref(),
+                            dither: args.dither,
+                            fit: args.fit,
+                            stdout: args.stdout,
+                            ratios: ratios.as_deref(),
+                        },
                     ),
-                )
+                );
+                progress.inc(1);
+                result
             })
             .collect();
 
+        progress.finish_and_clear();
+
         results
             .into_par_iter()
-            .for_each(|(path, result)| report_result(path, &result));
+            .for_each(|(path, result)| report_result(path, &result, args.stdout));
     }
 }
 
-fn report_result(path: &Path, result: &ImageResult<()>) {
-    match result {
-        Ok(_) => println!("✅ {}", path.display()),
-        Err(e) => println!("❌ {}: {}", path.display(), e),
+fn report_result(path: &Path, result: &ImageResult<()>, stdout: bool) {
+    // The bordered image itself may have been written to stdout, so status lines go to
+    // stderr in that mode to avoid corrupting the image stream.
+    let message = match result {
+        Ok(_) => format!("✅ {}", path.display()),
+        Err(e) => format!("❌ {}: {}", path.display(), e),
     };
+    if stdout {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
 }
@@ -1,16 +1,77 @@
 //! Image processing.
 use std::{
     ffi::OsString,
+    fs::File,
+    io::{self, stdin, BufWriter, Cursor, Read, Write},
     path::{Path, PathBuf},
 };
 
 use image::{
-    imageops::overlay, io::Reader, DynamicImage, GenericImageView, ImageResult, Rgba, RgbaImage,
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::{overlay, FilterType},
+    io::Reader,
+    ColorType, DynamicImage, GenericImageView, ImageEncoder, ImageResult, Rgba, RgbaImage,
 };
 use num::rational::Ratio;
 use once_cell::sync::Lazy;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
+/// Supported output image formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl Format {
+    /// The canonical file extension for this format.
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+            Format::Webp => "webp",
+        }
+    }
+
+    /// Guess the format from a file's extension, defaulting to PNG when unrecognized.
+    ///
+    /// # Args
+    /// * `file` - The image's file path.
+    fn infer(file: &Path) -> Self {
+        match file
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("jpg") | Some("jpeg") => Format::Jpeg,
+            Some("webp") => Format::Webp,
+            _ => Format::Png,
+        }
+    }
+}
+
+/// Built-in 16-color EGA-style palette, for use with `--palette ega`.
+pub const EGA_PALETTE: [Rgba<u8>; 16] = [
+    Rgba([0, 0, 0, 255]),
+    Rgba([0, 0, 170, 255]),
+    Rgba([0, 170, 0, 255]),
+    Rgba([0, 170, 170, 255]),
+    Rgba([170, 0, 0, 255]),
+    Rgba([170, 0, 170, 255]),
+    Rgba([170, 85, 0, 255]),
+    Rgba([170, 170, 170, 255]),
+    Rgba([85, 85, 85, 255]),
+    Rgba([85, 85, 255, 255]),
+    Rgba([85, 255, 85, 255]),
+    Rgba([85, 255, 255, 255]),
+    Rgba([255, 85, 85, 255]),
+    Rgba([255, 85, 255, 255]),
+    Rgba([255, 255, 85, 255]),
+    Rgba([255, 255, 255, 255]),
+];
+
 /// Common aspect ratios for images.
 pub static COMMON_RATIOS: Lazy<Vec<Ratio<u32>>> = Lazy::new(|| {
     vec![
@@ -26,7 +87,36 @@ pub static COMMON_RATIOS: Lazy<Vec<Ratio<u32>>> = Lazy::new(|| {
     ]
 });
 
-/// Process an image, adding a white border to it, and save it locally.
+/// Options controlling how an image is bordered and saved, beyond its source file path.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessOptions<'a> {
+    /// The border size in pixels.
+    pub border: u32,
+    /// The ratio to force, or none if the ratio should be guessed.
+    pub force_ratio: Option<Ratio<u32>>,
+    /// Whether to force the orientation when the ratio is forced.
+    pub force_orientation: bool,
+    /// The fill color of the border.
+    pub color: Rgba<u8>,
+    /// The output image format, or none if it should be inferred from the file extension.
+    pub format: Option<Format>,
+    /// The encoder quality, from 1 (worst) to 100 (best). Only applies to JPEG output.
+    pub quality: u8,
+    /// A fixed color palette to reduce the image to before bordering, or none to skip quantization.
+    pub palette: Option<&'a [Rgba<u8>]>,
+    /// Whether to apply Floyd-Steinberg dithering when quantizing. Only applies when `palette` is set.
+    pub dither: bool,
+    /// A `(width, height)` box to resize into before bordering, or none to only pad the native pixels.
+    /// When set, the final image is always exactly this size, regardless of `border` or `force_ratio`.
+    pub fit: Option<(u32, u32)>,
+    /// Whether to encode the bordered image to stdout instead of saving it to disk.
+    pub stdout: bool,
+    /// Candidate ratios to approximate against, or none to use `COMMON_RATIOS`.
+    /// Ignored when `force_ratio` is set.
+    pub ratios: Option<&'a [Ratio<u32>]>,
+}
+
+/// Process an image, adding a border to it, and save it locally.
 ///
 /// The final image will be adjusted to match the closest common image ratio,
 /// so the border size may not be respected along the smaller dimension.
@@ -37,39 +127,256 @@ pub static COMMON_RATIOS: Lazy<Vec<Ratio<u32>>> = Lazy::new(|| {
 ///
 /// # Args
 /// * `file` - The image's file path.
-/// * `border` - The border size in pixels.
-/// * `force_ratio` - The ratio to force, or none if the ratio should be guessed.
-/// * `force_orientation` - Whether to force the orientation when the ratio is forced.
-pub fn process_and_save_local(
+/// * `options` - The bordering, encoding, and output options to apply.
+///
+/// # Streaming
+/// Passing `Path::new("-")` as `file` reads the source image from stdin instead of opening
+/// a path, so `bpg` can be chained with other image tools (`cat in.png | bpg - --stdout`).
+/// When `options.stdout` is set, the bordered image is encoded to stdout and no
+/// `_bordered` file is written.
+///
+/// # Fit
+/// When `options.fit` is set, the ratio matching and border sizing described above do not
+/// apply: the image is resized to fit within the box and bordered onto a canvas of exactly
+/// that size.
+pub fn process_and_save_local(file: &Path, options: ProcessOptions) -> ImageResult<()> {
+    let image = decode(file)?;
+    let (image, final_dims) = match options.fit {
+        Some(target) => (resize_to_fit(&image, target), target),
+        None => {
+            let final_ratio = match options.force_ratio {
+                Some(ratio) => {
+                    if options.force_orientation {
+                        ratio
+                    } else {
+                        approximation(image.dimensions(), &[ratio, ratio.recip()])
+                    }
+                }
+                None => {
+                    approximation(image.dimensions(), options.ratios.unwrap_or(&COMMON_RATIOS))
+                }
+            };
+            let final_dims = adjust(image.dimensions(), options.border, final_ratio);
+            (image, final_dims)
+        }
+    };
+    // Quantize after resizing: resampling filters blend pixel colors, so quantizing first
+    // and resizing after reintroduces colors outside the palette.
+    let image = match options.palette {
+        Some(palette) => quantize(&image, palette, options.dither),
+        None => image,
+    };
+    let bordered = add_border(&image, final_dims, options.color);
+    save(
+        &bordered.to_rgba8(),
+        file,
+        options.format,
+        options.quality,
+        options.stdout,
+    )
+}
+
+/// Decode a source image, reading from stdin when `file` is `-`.
+///
+/// Stdin isn't seekable, but format-guessing needs to peek at the bytes, so stdin is
+/// buffered into memory first and wrapped in a `Cursor` rather than read directly.
+///
+/// # Args
+/// * `file` - The image's file path, or `-` to read from stdin.
+fn decode(file: &Path) -> ImageResult<DynamicImage> {
+    if file == Path::new("-") {
+        let mut buffer = Vec::new();
+        stdin().read_to_end(&mut buffer)?;
+        Reader::new(Cursor::new(buffer))
+            .with_guessed_format()?
+            .decode()
+    } else {
+        Reader::open(file)?.decode()
+    }
+}
+
+/// Resize an image to fit within a `target` box, preserving aspect ratio, using a Lanczos3 filter.
+///
+/// # Args
+/// * `image` - The source image.
+/// * `target` - The bounding box to fit within.
+///
+/// # Returns
+/// The resized image; one of its dimensions will match `target` exactly, the other will be
+/// smaller or equal.
+fn resize_to_fit(image: &DynamicImage, target: (u32, u32)) -> DynamicImage {
+    let (width, height) = fit_dimensions(image.dimensions(), target);
+    image.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// Compute the dimensions an image should be resized to, to fit within `target` while
+/// preserving aspect ratio.
+///
+/// Mirrors the approach `image::imageops::resize_dimensions` uses internally: the source
+/// and target width/height are cross-multiplied as `u64`s to decide which axis is binding,
+/// avoiding floating-point rounding and overflow.
+///
+/// # Args
+/// * `dims` - The source dimensions.
+/// * `target` - The bounding box to fit within.
+///
+/// # Returns
+/// The scaled dimensions; the binding axis matches `target` exactly.
+fn fit_dimensions(dims: (u32, u32), target: (u32, u32)) -> (u32, u32) {
+    let (width, height) = (dims.0 as u64, dims.1 as u64);
+    let (target_width, target_height) = (target.0 as u64, target.1 as u64);
+    if width * target_height <= height * target_width {
+        ((target_height * width / height) as u32, target_height as u32)
+    } else {
+        (target_width as u32, (target_width * height / width) as u32)
+    }
+}
+
+/// Reduce an image to the nearest colors in a fixed palette.
+///
+/// For each pixel, the palette entry minimizing squared Euclidean distance in RGB
+/// space is chosen. When `dither` is set, the per-pixel quantization error is
+/// spread to neighboring pixels with Floyd-Steinberg weights (7/16, 3/16, 5/16,
+/// 1/16) instead of being discarded, processing rows top-to-bottom and
+/// left-to-right so diffused error is read back correctly.
+///
+/// # Args
+/// * `image` - The source image.
+/// * `palette` - The fixed set of colors to reduce to.
+/// * `dither` - Whether to apply Floyd-Steinberg dithering.
+///
+/// # Returns
+/// The quantized image.
+fn quantize(image: &DynamicImage, palette: &[Rgba<u8>], dither: bool) -> DynamicImage {
+    let mut buffer = image.to_rgba8();
+    let (width, height) = buffer.dimensions();
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = *buffer.get_pixel(x, y);
+            let nearest = nearest_color(pixel, palette);
+            if dither {
+                let error = [
+                    pixel.0[0] as i32 - nearest.0[0] as i32,
+                    pixel.0[1] as i32 - nearest.0[1] as i32,
+                    pixel.0[2] as i32 - nearest.0[2] as i32,
+                ];
+                diffuse_error(&mut buffer, x, y, width, height, error);
+            }
+            buffer.put_pixel(x, y, nearest);
+        }
+    }
+    DynamicImage::ImageRgba8(buffer)
+}
+
+/// Find the palette entry closest to a pixel, by squared Euclidean RGB distance.
+fn nearest_color(pixel: Rgba<u8>, palette: &[Rgba<u8>]) -> Rgba<u8> {
+    *palette
+        .iter()
+        .min_by_key(|candidate| squared_distance(pixel, **candidate))
+        .expect("palette is non-empty")
+}
+
+/// Squared Euclidean distance between two colors' RGB channels.
+fn squared_distance(a: Rgba<u8>, b: Rgba<u8>) -> u32 {
+    let dr = a.0[0] as i32 - b.0[0] as i32;
+    let dg = a.0[1] as i32 - b.0[1] as i32;
+    let db = a.0[2] as i32 - b.0[2] as i32;
+    (dr * dr + dg * dg + db * db) as u32
+}
+
+/// Diffuse a pixel's per-channel quantization error to its not-yet-visited
+/// neighbors, using Floyd-Steinberg weights. Out-of-bounds neighbors are skipped.
+fn diffuse_error(buffer: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, error: [i32; 3]) {
+    let weighted_neighbors: [(i64, i64, i32); 4] =
+        [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+    for (dx, dy, weight) in weighted_neighbors {
+        let nx = x as i64 + dx;
+        let ny = y as i64 + dy;
+        if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+            continue;
+        }
+        let mut neighbor = *buffer.get_pixel(nx as u32, ny as u32);
+        for (channel, error) in error.iter().enumerate() {
+            let adjusted = neighbor.0[channel] as i32 + error * weight / 16;
+            neighbor.0[channel] = adjusted.clamp(0, 255) as u8;
+        }
+        buffer.put_pixel(nx as u32, ny as u32, neighbor);
+    }
+}
+
+/// Encode a bordered image and write it to its destination: a sibling file, or stdout.
+///
+/// The image's format is explicitly resolved (rather than inferred from
+/// `DynamicImage::save`) so that `quality` can be honored for encoders that support it,
+/// and the image is converted to RGB when the target format has no alpha channel.
+///
+/// # Args
+/// * `image` - The bordered image.
+/// * `file` - The original image's file path.
+/// * `format` - The output image format, or none if it should be inferred from `file`'s extension.
+/// * `quality` - The encoder quality, from 1 (worst) to 100 (best). Only applies to JPEG output.
+/// * `stdout` - Whether to write to stdout instead of a `_bordered` sibling file.
+fn save(
+    image: &RgbaImage,
     file: &Path,
-    border: u32,
-    force_ratio: Option<Ratio<u32>>,
-    force_orientation: bool,
+    format: Option<Format>,
+    quality: u8,
+    stdout: bool,
 ) -> ImageResult<()> {
-    let image = Reader::open(file)?.decode()?;
-    let final_ratio = match force_ratio {
-        Some(ratio) => {
-            if force_orientation {
-                ratio
-            } else {
-                approximation(image.dimensions(), &[ratio, ratio.recip()])
-            }
+    let format = format.unwrap_or_else(|| Format::infer(file));
+    if stdout {
+        encode(image, BufWriter::new(io::stdout()), format, quality)
+    } else {
+        let writer = BufWriter::new(File::create(file_name(file, Some(format)))?);
+        encode(image, writer, format, quality)
+    }
+}
+
+/// Encode a bordered image with the encoder matching `format` and write it to `writer`.
+///
+/// # Args
+/// * `image` - The bordered image.
+/// * `writer` - The destination to write encoded bytes to.
+/// * `format` - The output image format.
+/// * `quality` - The encoder quality, from 1 (worst) to 100 (best). Only applies to JPEG output.
+fn encode<W: Write>(image: &RgbaImage, writer: W, format: Format, quality: u8) -> ImageResult<()> {
+    match format {
+        Format::Png => {
+            PngEncoder::new(writer).write_image(image, image.width(), image.height(), ColorType::Rgba8)
         }
-        None => approximation(image.dimensions(), &COMMON_RATIOS),
-    };
-    add_border(&image, adjust(image.dimensions(), border, final_ratio)).save(file_name(file))
+        Format::Jpeg => {
+            let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+            JpegEncoder::new_with_quality(writer, quality).write_image(
+                &rgb,
+                rgb.width(),
+                rgb.height(),
+                ColorType::Rgb8,
+            )
+        }
+        Format::Webp => {
+            WebPEncoder::new_lossless(writer).write_image(
+                image,
+                image.width(),
+                image.height(),
+                ColorType::Rgba8,
+            )
+        }
+    }
 }
 
 /// Generate a new file name for an image that is to be bordered.
 ///
 /// Currently just take the file stem and append "_bordered" to the end.
+/// When a `format` is given, the extension is normalized to match it;
+/// otherwise the original extension is kept as-is.
 ///
 /// # Args
 /// * `file` - The image's file path.
+/// * `format` - The output format, or none to keep the original extension.
 ///
 /// # Returns
 /// A new file path for the bordered image.
-fn file_name(file: &Path) -> PathBuf {
+fn file_name(file: &Path, format: Option<Format>) -> PathBuf {
     let mut new_path = PathBuf::new();
     new_path.push(file);
     if let Some(stem) = file.file_stem() {
@@ -78,20 +385,28 @@ fn file_name(file: &Path) -> PathBuf {
         new_stem.push("_bordered");
         new_path.set_file_name(new_stem);
     }
-    if let Some(extension) = file.extension() {
-        new_path.set_extension(extension);
+    match format {
+        Some(format) => {
+            new_path.set_extension(format.extension());
+        }
+        None => {
+            if let Some(extension) = file.extension() {
+                new_path.set_extension(extension);
+            }
+        }
     }
     new_path
 }
 
-/// Add a white border to an image, matching the final dimensions given.
+/// Add a border to an image, matching the final dimensions given.
 ///
 /// # Args
 /// * `image` - The original image.
 /// * `final_dims` - The final dimensions (width and height) of the bordered image.
-fn add_border(image: &DynamicImage, final_dims: (u32, u32)) -> DynamicImage {
+/// * `color` - The fill color of the border.
+fn add_border(image: &DynamicImage, final_dims: (u32, u32), color: Rgba<u8>) -> DynamicImage {
     let (width, height) = final_dims;
-    let mut background = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+    let mut background = RgbaImage::from_pixel(width, height, color);
     let x_offset = (width - image.width()) / 2;
     let y_offset = (height - image.height()) / 2;
     overlay(&mut background, image, x_offset as i64, y_offset as i64);
@@ -193,7 +508,46 @@ mod tests {
     #[case(Path::new(".png"), Path::new(".png_bordered"))]
     #[case(Path::new("test"), Path::new("test_bordered"))]
     fn test_file_name(#[case] input: &Path, #[case] expected: &Path) {
-        assert_eq!(file_name(input), expected);
+        assert_eq!(file_name(input, None), expected);
+    }
+
+    #[rstest]
+    #[case(Path::new("test.jpg"), Format::Png, Path::new("test_bordered.png"))]
+    #[case(Path::new("test.png"), Format::Jpeg, Path::new("test_bordered.jpg"))]
+    #[case(Path::new("test"), Format::Webp, Path::new("test_bordered.webp"))]
+    fn test_file_name_with_format(
+        #[case] input: &Path,
+        #[case] format: Format,
+        #[case] expected: &Path,
+    ) {
+        assert_eq!(file_name(input, Some(format)), expected);
+    }
+
+    #[rstest]
+    #[case(Path::new("test.jpg"), Format::Jpeg)]
+    #[case(Path::new("test.JPEG"), Format::Jpeg)]
+    #[case(Path::new("test.png"), Format::Png)]
+    #[case(Path::new("test.webp"), Format::Webp)]
+    #[case(Path::new("test"), Format::Png)]
+    #[case(Path::new("test.gif"), Format::Png)]
+    fn test_format_infer(#[case] file: &Path, #[case] expected: Format) {
+        assert_eq!(Format::infer(file), expected);
+    }
+
+    #[rstest]
+    #[case(Format::Png)]
+    #[case(Format::Jpeg)]
+    #[case(Format::Webp)]
+    fn test_encode_round_trip(base_image: DynamicImage, #[case] format: Format) {
+        let image = base_image.to_rgba8();
+        let mut buffer = Vec::new();
+        encode(&image, &mut buffer, format, 90).unwrap();
+        let decoded = Reader::new(Cursor::new(buffer))
+            .with_guessed_format()
+            .unwrap()
+            .decode()
+            .unwrap();
+        assert_eq!(decoded.dimensions(), image.dimensions());
     }
 
     #[rstest]
@@ -222,17 +576,85 @@ mod tests {
         #[case] final_dims: (u32, u32),
         #[case] expected: DynamicImage,
     ) {
-        assert_eq!(add_border(&base_image, final_dims), expected);
+        assert_eq!(
+            add_border(&base_image, final_dims, Rgba([255, 255, 255, 255])),
+            expected
+        );
     }
 
     #[rstest]
     fn test_add_border_transparent_base(#[with(Rgba([0, 0, 0, 0]))] base_image: DynamicImage) {
         assert_eq!(
-            add_border(&base_image, (12, 24)),
+            add_border(&base_image, (12, 24), Rgba([255, 255, 255, 255])),
             DynamicImage::ImageRgba8(RgbaImage::from_pixel(12, 24, Rgba([255, 255, 255, 255])))
         );
     }
 
+    #[rstest]
+    #[case((1000, 500), (800, 800), (800, 400))]
+    #[case((500, 1000), (800, 800), (400, 800))]
+    #[case((16, 9), (1600, 900), (1600, 900))]
+    #[case((9, 16), (900, 1600), (900, 1600))]
+    fn test_fit_dimensions(
+        #[case] dims: (u32, u32),
+        #[case] target: (u32, u32),
+        #[case] expected: (u32, u32),
+    ) {
+        assert_eq!(fit_dimensions(dims, target), expected);
+    }
+
+    #[rstest]
+    #[case(Rgba([0, 0, 0, 255]), Rgba([0, 0, 0, 255]), 0)]
+    #[case(Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255]), 255 * 255 * 3)]
+    #[case(Rgba([10, 20, 30, 255]), Rgba([13, 16, 30, 0]), 9 + 16)]
+    fn test_squared_distance(#[case] a: Rgba<u8>, #[case] b: Rgba<u8>, #[case] expected: u32) {
+        assert_eq!(squared_distance(a, b), expected);
+    }
+
+    #[rstest]
+    #[case(Rgba([10, 10, 10, 255]), &EGA_PALETTE, EGA_PALETTE[0])]
+    #[case(Rgba([255, 255, 255, 255]), &EGA_PALETTE, EGA_PALETTE[15])]
+    #[case(Rgba([0, 0, 160, 255]), &EGA_PALETTE, EGA_PALETTE[1])]
+    fn test_nearest_color(#[case] pixel: Rgba<u8>, #[case] palette: &[Rgba<u8>], #[case] expected: Rgba<u8>) {
+        assert_eq!(nearest_color(pixel, palette), expected);
+    }
+
+    #[rstest]
+    fn test_diffuse_error() {
+        let mut buffer = RgbaImage::from_pixel(2, 2, Rgba([10, 10, 10, 255]));
+        diffuse_error(&mut buffer, 0, 0, 2, 2, [16, -16, 0]);
+        assert_eq!(*buffer.get_pixel(1, 0), Rgba([17, 3, 10, 255]));
+        assert_eq!(*buffer.get_pixel(0, 1), Rgba([15, 5, 10, 255]));
+        assert_eq!(*buffer.get_pixel(1, 1), Rgba([11, 9, 10, 255]));
+        assert_eq!(*buffer.get_pixel(0, 0), Rgba([10, 10, 10, 255]));
+    }
+
+    #[rstest]
+    fn test_diffuse_error_out_of_bounds_skipped() {
+        let mut buffer = RgbaImage::from_pixel(2, 2, Rgba([10, 10, 10, 255]));
+        diffuse_error(&mut buffer, 1, 1, 2, 2, [16, -16, 0]);
+        assert_eq!(buffer, RgbaImage::from_pixel(2, 2, Rgba([10, 10, 10, 255])));
+    }
+
+    #[rstest]
+    fn test_quantize_no_dither() {
+        let palette = [Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])];
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_vec(
+            2,
+            1,
+            vec![10, 10, 10, 255, 240, 240, 240, 255],
+        ).unwrap());
+        let quantized = quantize(&image, &palette, false);
+        assert_eq!(
+            quantized,
+            DynamicImage::ImageRgba8(RgbaImage::from_vec(
+                2,
+                1,
+                vec![0, 0, 0, 255, 255, 255, 255, 255],
+            ).unwrap())
+        );
+    }
+
     #[rstest]
     #[case((0, 0), 10, Ratio::new(1, 10), (1, 10))]
     #[case((0, 0), 10, Ratio::new(10, 1), (100, 10))]
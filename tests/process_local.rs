@@ -1,12 +1,28 @@
 use std::{fs::remove_file, path::Path};
 
-use image::io::Reader;
+use image::{io::Reader, Rgba};
 
-use bpg::process::process_and_save_local;
+use bpg::process::{process_and_save_local, ProcessOptions};
 
 #[test]
 fn test_process_and_save_local_no_force_ratio_no_force_orientation() {
-    process_and_save_local(Path::new("tests/emoji.png"), 100, None, false).unwrap();
+    process_and_save_local(
+        Path::new("tests/emoji.png"),
+        ProcessOptions {
+            border: 100,
+            force_ratio: None,
+            force_orientation: false,
+            color: Rgba([255, 255, 255, 255]),
+            format: None,
+            quality: 90,
+            palette: None,
+            dither: false,
+            fit: None,
+            stdout: false,
+            ratios: None,
+        },
+    )
+    .unwrap();
     let result = Reader::open("tests/emoji_bordered.png")
         .unwrap()
         .decode()
@@ -18,3 +34,37 @@ fn test_process_and_save_local_no_force_ratio_no_force_orientation() {
     assert_eq!(result, expected);
     remove_file("tests/emoji_bordered.png").unwrap();
 }
+
+#[test]
+fn test_process_and_save_local_palette_and_fit() {
+    let palette = [Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])];
+    process_and_save_local(
+        Path::new("tests/emoji.png"),
+        ProcessOptions {
+            border: 0,
+            force_ratio: None,
+            force_orientation: false,
+            color: Rgba([255, 255, 255, 255]),
+            format: None,
+            quality: 90,
+            palette: Some(&palette),
+            dither: false,
+            fit: Some((64, 64)),
+            stdout: false,
+            ratios: None,
+        },
+    )
+    .unwrap();
+    let result = Reader::open("tests/emoji_bordered.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    for pixel in result.pixels() {
+        assert!(
+            palette.contains(pixel),
+            "pixel {pixel:?} falls outside the palette: resizing must happen before quantizing"
+        );
+    }
+    remove_file("tests/emoji_bordered.png").unwrap();
+}